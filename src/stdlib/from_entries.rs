@@ -2,25 +2,143 @@ use crate::compiler::prelude::*;
 use crate::prelude::{
     ArgumentList, Collection, Compiled, Example, Expression, FunctionCompileContext, kind,
 };
+use crate::value::kind::Index;
 use crate::value::{KeyString, ObjectMap};
+use std::str::FromStr;
 
-fn make_key_string(key: Value) -> ExpressionResult<KeyString> {
+pub(crate) fn make_key_string(key: Value, coerce_keys: bool) -> ExpressionResult<KeyString> {
     match key {
         Value::Bytes(key) => Ok(String::from_utf8_lossy(&key).into()),
+        Value::Null if coerce_keys => Ok("".into()),
+        Value::Integer(_) | Value::Float(_) | Value::Boolean(_) | Value::Timestamp(_)
+            if coerce_keys =>
+        {
+            Ok(key.to_string_lossy().into_owned().into())
+        }
         _ => Err("object keys must be strings".into()),
     }
 }
 
-fn from_entries(value: Value) -> Resolved {
+/// What to do when two entries in the input array share the same `"key"`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum KeyConflict {
+    /// Keep the last value seen for the key (the historical behavior).
+    #[default]
+    Last,
+    /// Keep the first value seen for the key.
+    First,
+    /// Collect every colliding value into an array, in the order seen.
+    Array,
+    /// Abort with an error as soon as a duplicate key is seen.
+    Error,
+}
+
+impl KeyConflict {
+    const fn all_value_strings() -> &'static [&'static str] {
+        &["last", "first", "array", "error"]
+    }
+}
+
+impl FromStr for KeyConflict {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "last" => Ok(Self::Last),
+            "first" => Ok(Self::First),
+            "array" => Ok(Self::Array),
+            "error" => Ok(Self::Error),
+            _ => Err("key_conflict must be one of \"last\", \"first\", \"array\", \"error\""),
+        }
+    }
+}
+
+/// The `Kind` of the `"value"` field of every entry the array is known to
+/// hold, or `None` if any entry isn't a known object shape (in which case
+/// callers should fall back to `any`).
+fn entries_value_kind(array: &Collection<Index>) -> Option<Kind> {
+    let entry_kind = |kind: &Kind| -> Option<Kind> {
+        if !kind.is_object() {
+            return None;
+        }
+        let object = kind.as_object()?;
+        Some(
+            object
+                .known()
+                .get(&"value".into())
+                .cloned()
+                // An entry without a `"value"` field contributes `null`.
+                .unwrap_or_else(|| object.unknown_kind().union(Kind::null())),
+        )
+    };
+
+    let mut value_kind = Kind::never();
+
+    for kind in array.known().values() {
+        value_kind = value_kind.union(entry_kind(kind)?);
+    }
+
+    let unknown = array.unknown_kind();
+    if !unknown.is_never() {
+        value_kind = value_kind.union(entry_kind(&unknown)?);
+    }
+
+    Some(value_kind)
+}
+
+fn from_entries(value: Value, key_conflict: KeyConflict, coerce_keys: bool) -> Resolved {
     let array = value.try_array()?;
     let mut object = ObjectMap::new();
+    // Tracks how many times each key has been seen under `KeyConflict::Array`,
+    // independent of the stored value's runtime type, so a legitimately
+    // array-valued entry can't be mistaken for an already-merged accumulator.
+    let mut array_occurrences: std::collections::HashMap<KeyString, usize> =
+        std::collections::HashMap::new();
 
     for entry in array {
         let mut entry = entry.try_object()?;
-        let key = entry.remove("key").unwrap_or(Value::Null);
+        let key = match entry.remove("key") {
+            Some(key) => make_key_string(key, coerce_keys)?,
+            None => return Err("object keys must be strings".into()),
+        };
         let value = entry.remove("value").unwrap_or(Value::Null);
-        let key = make_key_string(key)?;
-        object.insert(key, value);
+
+        match key_conflict {
+            KeyConflict::Last => {
+                object.insert(key, value);
+            }
+            KeyConflict::First => {
+                if !object.contains_key(&key) {
+                    object.insert(key, value);
+                }
+            }
+            KeyConflict::Array => {
+                let occurrences = array_occurrences.entry(key.clone()).or_insert(0);
+                *occurrences += 1;
+
+                match *occurrences {
+                    1 => {
+                        object.insert(key, value);
+                    }
+                    2 => {
+                        let existing = object.remove(&key).expect("inserted on first occurrence");
+                        object.insert(key, Value::Array(vec![existing, value]));
+                    }
+                    _ => {
+                        let Some(Value::Array(values)) = object.get_mut(&key) else {
+                            unreachable!("wrapped into an array on the second occurrence");
+                        };
+                        values.push(value);
+                    }
+                }
+            }
+            KeyConflict::Error => {
+                if object.contains_key(&key) {
+                    return Err(format!("duplicate key `{key}` found while merging entries").into());
+                }
+                object.insert(key, value);
+            }
+        }
     }
 
     Ok(Value::Object(object))
@@ -51,13 +169,29 @@ impl Function for FromEntries {
     }
 
     fn parameters(&self) -> &'static [Parameter] {
-        &[Parameter {
-            keyword: "value",
-            kind: kind::ARRAY,
-            required: true,
-            description: "The array of key/value objects to convert.",
-            default: None,
-        }]
+        &[
+            Parameter {
+                keyword: "value",
+                kind: kind::ARRAY,
+                required: true,
+                description: "The array of key/value objects to convert.",
+                default: None,
+            },
+            Parameter {
+                keyword: "key_conflict",
+                kind: kind::BYTES,
+                required: false,
+                description: "How to handle two entries sharing the same `key`. \"last\" keeps the last value seen, \"first\" keeps the first, \"array\" collects every colliding value into an array, and \"error\" aborts with an error.",
+                default: Some("last"),
+            },
+            Parameter {
+                keyword: "coerce_keys",
+                kind: kind::BOOLEAN,
+                required: false,
+                description: "If `true`, non-string keys (integers, floats, booleans, timestamps, and `null`) are stringified using the same rules as `to_string`, instead of raising an error.",
+                default: Some("false"),
+            },
+        ]
     }
 
     fn examples(&self) -> &'static [Example] {
@@ -72,33 +206,79 @@ impl Function for FromEntries {
                 source: r#"from_entries([{ "key": "foo", "value": "bar" }])"#,
                 result: Ok(r#"{ "foo": "bar" }"#),
             },
+            example! {
+                title: "Collect duplicate keys into an array",
+                source: r#"from_entries([{ "key": "foo", "value": "bar" }, { "key": "foo", "value": "baz" }], key_conflict: "array")"#,
+                result: Ok(r#"{ "foo": ["bar", "baz"] }"#),
+            },
+            example! {
+                title: "Coerce non-string keys",
+                source: r#"from_entries([{ "key": 1, "value": "bar" }], coerce_keys: true)"#,
+                result: Ok(r#"{ "1": "bar" }"#),
+            },
         ]
     }
 
     fn compile(
         &self,
-        _state: &state::TypeState,
+        state: &state::TypeState,
         _ctx: &mut FunctionCompileContext,
         arguments: ArgumentList,
     ) -> Compiled {
         let value = arguments.required("value");
-        Ok(FromEntriesFn { value }.as_expr())
+        let key_conflict = arguments
+            .optional_enum("key_conflict", KeyConflict::all_value_strings(), state)?
+            .map(|s| KeyConflict::from_str(&s).expect("validated by optional_enum"))
+            .unwrap_or_default();
+        let coerce_keys = arguments
+            .optional("coerce_keys")
+            .unwrap_or_else(|| expr!(false));
+
+        Ok(FromEntriesFn {
+            value,
+            key_conflict,
+            coerce_keys,
+        }
+        .as_expr())
     }
 }
 
 #[derive(Clone, Debug)]
 struct FromEntriesFn {
     value: Box<dyn Expression>,
+    key_conflict: KeyConflict,
+    coerce_keys: Box<dyn Expression>,
 }
 
 impl FunctionExpression for FromEntriesFn {
     fn resolve(&self, ctx: &mut Context) -> Resolved {
         let value = self.value.resolve(ctx)?;
-        from_entries(value)
+        let coerce_keys = self.coerce_keys.resolve(ctx)?.try_boolean()?;
+        from_entries(value, self.key_conflict, coerce_keys)
     }
 
-    fn type_def(&self, _state: &TypeState) -> TypeDef {
-        TypeDef::object(Collection::any())
+    fn type_def(&self, state: &TypeState) -> TypeDef {
+        let value_kind = self.value.type_def(state).into_kind();
+
+        let array = match value_kind.as_array().filter(|_| value_kind.is_array()) {
+            Some(array) => array,
+            None => return TypeDef::object(Collection::any()),
+        };
+
+        let object_tdef = match entries_value_kind(array) {
+            // A key seen more than once holds an array of all its values.
+            Some(value_kind) if self.key_conflict == KeyConflict::Array => {
+                let collected = Kind::array(Collection::from_unknown(value_kind.clone()));
+                TypeDef::object(Collection::from_unknown(value_kind.union(collected)))
+            }
+            Some(value_kind) => TypeDef::object(Collection::from_unknown(value_kind)),
+            None => TypeDef::object(Collection::any()),
+        };
+
+        match self.key_conflict {
+            KeyConflict::Error => object_tdef.fallible(),
+            KeyConflict::Last | KeyConflict::First | KeyConflict::Array => object_tdef,
+        }
     }
 }
 
@@ -107,25 +287,37 @@ mod test {
     use super::*;
     use crate::value;
 
+    fn object_tdef(value_kind: Kind) -> TypeDef {
+        TypeDef::object(Collection::from_unknown(value_kind))
+    }
+
+    fn array_conflict_tdef(value_kind: Kind) -> TypeDef {
+        object_tdef(
+            value_kind
+                .clone()
+                .union(Kind::array(Collection::from_unknown(value_kind))),
+        )
+    }
+
     test_function![
         from_entries => FromEntries;
 
         empty_array {
             args: func_args![value: value!([])],
             want: Ok(value!({})),
-            tdef: TypeDef::object(Collection::any()),
+            tdef: object_tdef(Kind::never()),
         }
 
         array {
             args: func_args![value: value!([{key: "foo", value: "bar"}])],
             want: Ok(value!({foo: "bar"})),
-            tdef: TypeDef::object(Collection::any()),
+            tdef: object_tdef(Kind::bytes()),
         }
 
         missing_value_defaults_to_null {
             args: func_args![value: value!([{key: "foo"}])],
             want: Ok(value!({foo: null})),
-            tdef: TypeDef::object(Collection::any()),
+            tdef: object_tdef(Kind::null()),
         }
 
         non_array {
@@ -143,7 +335,128 @@ mod test {
         key_not_string {
             args: func_args![value: value!([{key: 1, value: "bar"}])],
             want: Err("object keys must be strings"),
-            tdef: TypeDef::object(Collection::any()),
+            tdef: object_tdef(Kind::bytes()),
+        }
+
+        duplicate_key_defaults_to_last {
+            args: func_args![value: value!([{key: "foo", value: "bar"}, {key: "foo", value: "baz"}])],
+            want: Ok(value!({foo: "baz"})),
+            tdef: object_tdef(Kind::bytes()),
+        }
+
+        duplicate_key_first {
+            args: func_args![
+                value: value!([{key: "foo", value: "bar"}, {key: "foo", value: "baz"}]),
+                key_conflict: "first",
+            ],
+            want: Ok(value!({foo: "bar"})),
+            tdef: object_tdef(Kind::bytes()),
+        }
+
+        duplicate_key_array {
+            args: func_args![
+                value: value!([{key: "foo", value: "bar"}, {key: "foo", value: "baz"}]),
+                key_conflict: "array",
+            ],
+            want: Ok(value!({foo: ["bar", "baz"]})),
+            tdef: array_conflict_tdef(Kind::bytes()),
+        }
+
+        duplicate_key_array_with_array_valued_entries {
+            args: func_args![
+                value: value!([{key: "foo", value: [1, 2]}, {key: "foo", value: [3, 4]}]),
+                key_conflict: "array",
+            ],
+            want: Ok(value!({foo: [[1, 2], [3, 4]]})),
+            tdef: array_conflict_tdef(Kind::array(Collection::from(std::collections::BTreeMap::from([
+                (Index::from(0), Kind::integer()),
+                (Index::from(1), Kind::integer()),
+            ])))),
+        }
+
+        triplicate_key_array {
+            args: func_args![
+                value: value!([
+                    {key: "foo", value: "a"},
+                    {key: "foo", value: "b"},
+                    {key: "foo", value: "c"},
+                ]),
+                key_conflict: "array",
+            ],
+            want: Ok(value!({foo: ["a", "b", "c"]})),
+            tdef: array_conflict_tdef(Kind::bytes()),
+        }
+
+        duplicate_key_error {
+            args: func_args![
+                value: value!([{key: "foo", value: "bar"}, {key: "foo", value: "baz"}]),
+                key_conflict: "error",
+            ],
+            want: Err("duplicate key `foo` found while merging entries"),
+            tdef: object_tdef(Kind::bytes()).fallible(),
+        }
+
+        integer_key_rejected_with_coerce_keys_explicitly_false {
+            args: func_args![
+                value: value!([{key: 1, value: "bar"}]),
+                coerce_keys: false,
+            ],
+            want: Err("object keys must be strings"),
+            tdef: object_tdef(Kind::bytes()),
+        }
+
+        coerce_integer_key {
+            args: func_args![
+                value: value!([{key: 1, value: "bar"}]),
+                coerce_keys: true,
+            ],
+            want: Ok(value!({"1": "bar"})),
+            tdef: object_tdef(Kind::bytes()),
+        }
+
+        coerce_boolean_key {
+            args: func_args![
+                value: value!([{key: true, value: "bar"}]),
+                coerce_keys: true,
+            ],
+            want: Ok(value!({"true": "bar"})),
+            tdef: object_tdef(Kind::bytes()),
+        }
+
+        coerce_null_key {
+            args: func_args![
+                value: value!([{key: null, value: "bar"}]),
+                coerce_keys: true,
+            ],
+            want: Ok(value!({"": "bar"})),
+            tdef: object_tdef(Kind::bytes()),
+        }
+
+        coerce_float_key {
+            args: func_args![
+                value: value!([{key: 1.5, value: "bar"}]),
+                coerce_keys: true,
+            ],
+            want: Ok(value!({"1.5": "bar"})),
+            tdef: object_tdef(Kind::bytes()),
+        }
+
+        coerce_timestamp_key {
+            args: func_args![
+                value: value!([{key: t!("2021-02-03T04:05:06Z"), value: "bar"}]),
+                coerce_keys: true,
+            ],
+            want: Ok(value!({"2021-02-03T04:05:06Z": "bar"})),
+            tdef: object_tdef(Kind::bytes()),
+        }
+
+        missing_key_still_errors_with_coerce_keys {
+            args: func_args![
+                value: value!([{value: "bar"}]),
+                coerce_keys: true,
+            ],
+            want: Err("object keys must be strings"),
+            tdef: object_tdef(Kind::bytes()),
         }
     ];
 }
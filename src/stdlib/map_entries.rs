@@ -0,0 +1,310 @@
+use crate::compiler::prelude::*;
+use crate::prelude::{
+    ArgumentList, Collection, Compiled, Example, Expression, FunctionCompileContext, kind,
+};
+use crate::value::kind::Index;
+use crate::value::{KeyString, ObjectMap};
+
+use super::from_entries::make_key_string;
+
+/// The `Kind` the closure produces for the `"value"` half of an entry,
+/// whether the closure returns a `[key, value]` array or a `{ key, value }`
+/// object.
+fn closure_value_kind(kind: &Kind) -> Kind {
+    if let Some(array) = kind.as_array().filter(|_| kind.is_array()) {
+        return array
+            .known()
+            .get(&Index::from(1))
+            .cloned()
+            .unwrap_or_else(|| array.unknown_kind());
+    }
+
+    if let Some(object) = kind.as_object().filter(|_| kind.is_object()) {
+        return object
+            .known()
+            .get(&"value".into())
+            .cloned()
+            // An object without a `"value"` field yields `null`.
+            .unwrap_or_else(|| object.unknown_kind().union(Kind::null()));
+    }
+
+    Kind::any()
+}
+
+fn entry_from_closure_output(output: Value) -> ExpressionResult<(KeyString, Value)> {
+    match output {
+        Value::Array(mut items) if items.len() == 2 => {
+            let new_value = items.pop().expect("length checked above");
+            let new_key = items.pop().expect("length checked above");
+            Ok((make_key_string(new_key, false)?, new_value))
+        }
+        Value::Object(mut entry) => {
+            let new_key = entry
+                .remove("key")
+                .ok_or("closure must return a `key` field")?;
+            let new_value = entry.remove("value").unwrap_or(Value::Null);
+            Ok((make_key_string(new_key, false)?, new_value))
+        }
+        _ => Err("closure must return a two-element array or a `{ key, value }` object".into()),
+    }
+}
+
+/// Folds rewritten entries into an object, later entries overwriting earlier
+/// ones that map onto the same key.
+fn build_object(entries: impl IntoIterator<Item = (KeyString, Value)>) -> Value {
+    let mut result = ObjectMap::new();
+
+    for (key, value) in entries {
+        result.insert(key, value);
+    }
+
+    Value::Object(result)
+}
+
+fn map_entries(value: Value, ctx: &mut Context, closure: &FunctionClosure) -> Resolved {
+    let object = value.try_object()?;
+    let mut entries = Vec::with_capacity(object.len());
+
+    for (key, value) in object {
+        let output = closure.run_key_value(ctx, key, value)?;
+        entries.push(entry_from_closure_output(output)?);
+    }
+
+    Ok(build_object(entries))
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MapEntries;
+
+impl Function for MapEntries {
+    fn identifier(&self) -> &'static str {
+        "map_entries"
+    }
+
+    fn usage(&self) -> &'static str {
+        "Rewrites both the keys and the values of an object in a single pass."
+    }
+
+    fn category(&self) -> &'static str {
+        Category::Object.as_ref()
+    }
+
+    fn return_kind(&self) -> u16 {
+        kind::OBJECT
+    }
+
+    fn return_rules(&self) -> &'static [&'static str] {
+        &["The returned object has the same number of entries as `value`, unless the closure maps two different keys onto the same key, in which case the later entry wins."]
+    }
+
+    fn parameters(&self) -> &'static [Parameter] {
+        &[Parameter {
+            keyword: "value",
+            kind: kind::OBJECT,
+            required: true,
+            description: "The object to manipulate.",
+            default: None,
+        }]
+    }
+
+    fn examples(&self) -> &'static [Example] {
+        &[example! {
+            title: "Rewrite keys and values",
+            source: r#"map_entries({ "foo": "bar" }) -> |key, value| { [upcase!(key), upcase!(value)] }"#,
+            result: Ok(r#"{ "FOO": "BAR" }"#),
+        }]
+    }
+
+    fn compile(
+        &self,
+        _state: &state::TypeState,
+        _ctx: &mut FunctionCompileContext,
+        arguments: ArgumentList,
+    ) -> Compiled {
+        let value = arguments.required("value");
+        let closure = arguments.required_closure()?;
+
+        Ok(MapEntriesFn { value, closure }.as_expr())
+    }
+
+    fn closure(&self) -> Option<closure::Definition> {
+        use closure::{Definition, Input, Output, Variable};
+
+        Some(Definition {
+            inputs: vec![Input {
+                parameter_keyword: "value",
+                kind: Kind::object(Collection::any()),
+                variables: vec![Variable { kind: Kind::bytes() }, Variable { kind: Kind::any() }],
+                output: Output::Kind(
+                    Kind::array(Collection::any()).union(Kind::object(Collection::any())),
+                ),
+                example: Example {
+                    title: "Rewrite keys and values",
+                    source: r#"map_entries({ "foo": "bar" }) -> |key, value| { [upcase!(key), upcase!(value)] }"#,
+                    result: Ok(r#"{ "FOO": "BAR" }"#),
+                },
+            }],
+            is_iterator: true,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+struct MapEntriesFn {
+    value: Box<dyn Expression>,
+    closure: FunctionClosure,
+}
+
+impl FunctionExpression for MapEntriesFn {
+    fn resolve(&self, ctx: &mut Context) -> Resolved {
+        let value = self.value.resolve(ctx)?;
+        map_entries(value, ctx, &self.closure)
+    }
+
+    fn type_def(&self, state: &TypeState) -> TypeDef {
+        let return_kind = self.closure.block.type_def(state).into_kind();
+        let value_kind = closure_value_kind(&return_kind);
+
+        TypeDef::object(Collection::from_unknown(value_kind)).fallible()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::value;
+
+    test_function![
+        map_entries => MapEntries;
+
+        empty_object {
+            args: func_args![value: value!({})],
+            closure: r#"|key, value| { [key, value] }"#,
+            want: Ok(value!({})),
+            tdef: TypeDef::object(Collection::from_unknown(Kind::any())).fallible(),
+        }
+
+        rewrite_keys_and_values_via_array {
+            args: func_args![value: value!({foo: "bar"})],
+            closure: r#"|key, value| { [upcase!(key), upcase!(value)] }"#,
+            want: Ok(value!({FOO: "BAR"})),
+            tdef: TypeDef::object(Collection::from_unknown(Kind::bytes())).fallible(),
+        }
+
+        rewrite_keys_and_values_via_object {
+            args: func_args![value: value!({foo: "bar"})],
+            closure: r#"|key, value| { { "key": upcase!(key), "value": upcase!(value) } }"#,
+            want: Ok(value!({FOO: "BAR"})),
+            tdef: TypeDef::object(Collection::from_unknown(Kind::bytes())).fallible(),
+        }
+
+        non_object_value_errors {
+            args: func_args![value: value!(true)],
+            closure: r#"|key, value| { [key, value] }"#,
+            want: Err("expected object, got boolean"),
+            tdef: TypeDef::object(Collection::any()).fallible(),
+        }
+
+        closure_wrong_array_length_errors {
+            args: func_args![value: value!({foo: "bar"})],
+            closure: r#"|key, value| { [key] }"#,
+            want: Err("closure must return a two-element array or a `{ key, value }` object"),
+            tdef: TypeDef::object(Collection::from_unknown(Kind::never())).fallible(),
+        }
+    ];
+
+    #[test]
+    fn entry_from_array_output() {
+        let output = value!(["FOO", "BAR"]);
+        let (key, value) = entry_from_closure_output(output).unwrap();
+        assert_eq!(key, KeyString::from("FOO"));
+        assert_eq!(value, Value::from("BAR"));
+    }
+
+    #[test]
+    fn entry_from_object_output() {
+        let output = value!({key: "FOO", value: "BAR"});
+        let (key, value) = entry_from_closure_output(output).unwrap();
+        assert_eq!(key, KeyString::from("FOO"));
+        assert_eq!(value, Value::from("BAR"));
+    }
+
+    #[test]
+    fn entry_from_object_output_missing_value_defaults_to_null() {
+        let output = value!({key: "FOO"});
+        let (key, value) = entry_from_closure_output(output).unwrap();
+        assert_eq!(key, KeyString::from("FOO"));
+        assert_eq!(value, Value::Null);
+    }
+
+    #[test]
+    fn entry_from_object_output_missing_key_errors() {
+        let output = value!({value: "BAR"});
+        let error = entry_from_closure_output(output).unwrap_err();
+        assert_eq!(error.to_string(), "closure must return a `key` field");
+    }
+
+    #[test]
+    fn entry_from_array_output_wrong_length_errors() {
+        let output = value!(["FOO"]);
+        let error = entry_from_closure_output(output).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "closure must return a two-element array or a `{ key, value }` object"
+        );
+    }
+
+    #[test]
+    fn entry_from_non_string_key_errors() {
+        let output = value!([1, "BAR"]);
+        let error = entry_from_closure_output(output).unwrap_err();
+        assert_eq!(error.to_string(), "object keys must be strings");
+    }
+
+    #[test]
+    fn entry_from_scalar_output_errors() {
+        let output = value!(true);
+        let error = entry_from_closure_output(output).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "closure must return a two-element array or a `{ key, value }` object"
+        );
+    }
+
+    #[test]
+    fn build_object_last_collision_wins() {
+        let entries = vec![
+            (KeyString::from("foo"), Value::from("bar")),
+            (KeyString::from("foo"), Value::from("baz")),
+        ];
+        assert_eq!(build_object(entries), value!({foo: "baz"}));
+    }
+
+    #[test]
+    fn build_object_empty() {
+        assert_eq!(build_object(Vec::new()), value!({}));
+    }
+
+    #[test]
+    fn closure_value_kind_from_array_output() {
+        let kind = Kind::array(Collection::from(std::collections::BTreeMap::from([
+            (Index::from(0), Kind::bytes()),
+            (Index::from(1), Kind::integer()),
+        ])));
+        assert_eq!(closure_value_kind(&kind), Kind::integer());
+    }
+
+    #[test]
+    fn closure_value_kind_from_object_output() {
+        let kind = Kind::object(Collection::from(std::collections::BTreeMap::from([
+            ("key".into(), Kind::bytes()),
+            ("value".into(), Kind::boolean()),
+        ])));
+        assert_eq!(closure_value_kind(&kind), Kind::boolean());
+    }
+
+    #[test]
+    fn closure_value_kind_falls_back_to_any() {
+        assert_eq!(closure_value_kind(&Kind::bytes()), Kind::any());
+    }
+}
@@ -4,7 +4,9 @@ use crate::example;
 use crate::prelude::{
     ArgumentList, Collection, Compiled, Example, Expression, FunctionCompileContext, kind,
 };
+use crate::value::kind::Field;
 use crate::value::{KeyString, ObjectMap};
+use std::collections::BTreeMap;
 
 #[derive(Clone, Debug, Copy)]
 pub struct ToEntries;
@@ -19,6 +21,15 @@ fn to_entries(value: Value) -> Resolved {
     Ok(Value::Array(object.into_iter().map(build_entry).collect()))
 }
 
+/// The union of the `Kind` of every known field plus the fallback kind for
+/// any field the collection doesn't know about.
+fn merged_value_kind(object: &Collection<Field>) -> Kind {
+    object
+        .known()
+        .values()
+        .fold(object.unknown_kind(), |acc, kind| acc.union(kind.clone()))
+}
+
 impl Function for ToEntries {
     fn identifier(&self) -> &'static str {
         "to_entries"
@@ -87,8 +98,19 @@ impl FunctionExpression for ToEntriesFn {
         to_entries(value)
     }
 
-    fn type_def(&self, _state: &TypeState) -> TypeDef {
-        TypeDef::array(Collection::any())
+    fn type_def(&self, state: &TypeState) -> TypeDef {
+        let value_kind = self.value.type_def(state).into_kind();
+
+        let Some(object) = value_kind.as_object().filter(|_| value_kind.is_object()) else {
+            return TypeDef::array(Collection::any());
+        };
+
+        let entry = Collection::from(BTreeMap::from([
+            (Field::from("key"), Kind::bytes()),
+            (Field::from("value"), merged_value_kind(object)),
+        ]));
+
+        TypeDef::array(Collection::from_unknown(Kind::object(entry)))
     }
 }
 
@@ -97,19 +119,27 @@ mod test {
     use super::*;
     use crate::value;
 
+    fn entry_tdef(value_kind: Kind) -> TypeDef {
+        let entry = Collection::from(BTreeMap::from([
+            (Field::from("key"), Kind::bytes()),
+            (Field::from("value"), value_kind),
+        ]));
+        TypeDef::array(Collection::from_unknown(Kind::object(entry)))
+    }
+
     test_function![
         to_entries => ToEntries;
 
         empty_object {
             args: func_args![value: value!({})],
             want: Ok(value!([])),
-            tdef: TypeDef::array(Collection::any()),
+            tdef: entry_tdef(Kind::never()),
         }
 
         object {
             args: func_args![value: value!({foo: "bar"})],
             want: Ok(value!([{key: "foo", value: "bar"}])),
-            tdef: TypeDef::array(Collection::any()),
+            tdef: entry_tdef(Kind::bytes()),
         }
 
         non_object {